@@ -0,0 +1,110 @@
+use anyhow::Result;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+
+/// NVS namespace and key used to persist the application config blob.
+const NVS_NAMESPACE: &str = "app_cfg";
+const NVS_KEY: &str = "cfg";
+
+/// Persisted counterpart of [`crate::wifi::StaIpConfig`]. Kept as a separate,
+/// serializable type rather than reusing `StaIpConfig` directly since the
+/// latter lives in `wifi` and has no reason to depend on serde.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct StaIpSettings {
+    pub ip: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    /// Subnet prefix length, e.g. 24 for a `/24` (255.255.255.0).
+    pub prefix_len: u8,
+}
+
+impl From<StaIpSettings> for crate::wifi::StaIpConfig {
+    fn from(s: StaIpSettings) -> Self {
+        Self {
+            ip: s.ip,
+            gateway: s.gateway,
+            prefix_len: s.prefix_len,
+        }
+    }
+}
+
+/// Runtime configuration that survives a reboot: AP credentials/channel, the
+/// optional home/STA network to join, and the sensor update period. Loaded
+/// once at boot and rewritten whenever the provisioning endpoint accepts new
+/// settings.
+///
+/// AP and STA credentials are deliberately separate fields (rather than one
+/// shared `ssid`/`password` pair) since they name two different networks:
+/// the demo's own AP, always present, and an optional upstream network the
+/// board joins as a client.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AppConfig {
+    pub ap_ssid: String,
+    pub ap_password: Option<String>,
+    pub channel: u8,
+    pub update_period_secs: u64,
+    /// Home/upstream network to join as STA, if provisioned. `None` keeps
+    /// the board AP-only.
+    #[serde(default)]
+    pub sta_ssid: Option<String>,
+    #[serde(default)]
+    pub sta_password: Option<String>,
+    /// Fixed STA IP/gateway to apply on connect; `None` uses DHCP.
+    #[serde(default)]
+    pub sta_ip: Option<StaIpSettings>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            ap_ssid: "ESP32-S3-DEMO".to_string(),
+            ap_password: Some("password123".to_string()),
+            channel: 1,
+            update_period_secs: 5,
+            sta_ssid: None,
+            sta_password: None,
+            sta_ip: None,
+        }
+    }
+}
+
+/// Load the persisted config from NVS, falling back to [`AppConfig::default`]
+/// if the namespace/key is absent, empty or fails to parse.
+pub fn load() -> AppConfig {
+    match load_inner() {
+        Ok(Some(cfg)) => cfg,
+        Ok(None) => {
+            log::info!("No persisted config found, using defaults");
+            AppConfig::default()
+        }
+        Err(e) => {
+            log::warn!("Failed to load config ({e}), using defaults");
+            AppConfig::default()
+        }
+    }
+}
+
+fn load_inner() -> Result<Option<AppConfig>> {
+    let nvs_part = EspDefaultNvsPartition::take()?;
+    let nvs: EspNvs<NvsDefault> = EspNvs::new(nvs_part, NVS_NAMESPACE, true)?;
+
+    // get_blob needs a buffer; the config blob is small, a few hundred bytes is ample.
+    let mut buf = [0u8; 512];
+    let Some(bytes) = nvs.get_blob(NVS_KEY, &mut buf)? else {
+        return Ok(None);
+    };
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_slice(bytes)?))
+}
+
+/// Persist `cfg` to NVS so it survives the next power cycle.
+pub fn save(cfg: &AppConfig) -> Result<()> {
+    let nvs_part = EspDefaultNvsPartition::take()?;
+    let mut nvs: EspNvs<NvsDefault> = EspNvs::new(nvs_part, NVS_NAMESPACE, true)?;
+    let bytes = serde_json::to_vec(cfg)?;
+    nvs.set_blob(NVS_KEY, &bytes)?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use esp_idf_svc::espnow::EspNow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::sensor::SensorData;
+
+/// Latest reading received from each ESP-NOW peer, keyed by its 6-byte MAC.
+/// Kept separate from `SharedSensor` (the board's own reading) since peers
+/// are an open-ended, arrive-whenever set rather than a single fixed value.
+pub type SharedNodes = Arc<Mutex<HashMap<[u8; 6], SensorData>>>;
+
+/// Create an empty peer map to hand to `start_espnow` and `http::start_http_server`.
+pub fn new_shared_nodes() -> SharedNodes {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+/// Register an ESP-NOW receive callback that decodes incoming frames as JSON
+/// `SensorData` and merges each sender's latest reading into `nodes`.
+///
+/// ESP-NOW needs no AP association and works alongside the existing Wi-Fi AP
+/// on the same channel, so this can run regardless of provisioning state.
+/// The returned handle must be kept alive for as long as reception is wanted.
+pub fn start_espnow(nodes: SharedNodes) -> Result<EspNow<'static>> {
+    let espnow = EspNow::take()?;
+
+    espnow.register_recv_cb(move |mac, data| {
+        let mac: [u8; 6] = match mac.try_into() {
+            Ok(mac) => mac,
+            Err(_) => {
+                log::warn!("Ignoring ESP-NOW frame with unexpected MAC length");
+                return;
+            }
+        };
+
+        match serde_json::from_slice::<SensorData>(data) {
+            Ok(reading) => {
+                if let Ok(mut nodes) = nodes.lock() {
+                    nodes.insert(mac, reading);
+                } else {
+                    log::warn!("Failed to lock ESP-NOW nodes map");
+                }
+            }
+            Err(e) => log::warn!("Failed to parse ESP-NOW frame from {}: {e}", mac_to_hex(&mac)),
+        }
+    })?;
+
+    log::info!("ESP-NOW receiver registered");
+    Ok(espnow)
+}
+
+/// Format a MAC address as `aa:bb:cc:dd:ee:ff`, used for both logging and the
+/// `/api/nodes` JSON keys (serde_json object keys must be strings).
+pub fn mac_to_hex(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
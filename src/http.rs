@@ -1,33 +1,125 @@
 use anyhow::Result;
 use esp_idf_svc::http::server::EspHttpServer;
 use esp_idf_svc::http::Method;
+use esp_idf_svc::ws::FrameType;
+use serde::Deserialize;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
-use crate::sensor::{snapshot, SharedSensor};
+use crate::config;
+use crate::espnow::{mac_to_hex, SharedNodes};
+use crate::ota;
+use crate::sensor::{snapshot, SensorData, SharedSensor};
+use crate::wifi::{drop_sta, reconnect_sta_with_timeout, SharedWifi};
 
 /// Static index page embedded at compile-time from the assets directory.
 /// Adjust the path if you move the file.
 const INDEX_HTML: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/index.html"));
 
+/// Upper bound on request bodies read via `read_to_end`, so a misbehaving or
+/// malicious client can't exhaust heap on a memory-constrained device.
+const MAX_BODY_LEN: u64 = 4096;
+
+/// How long the background reconnect in `/api/wifi` waits for the new
+/// network to come up before giving up and restoring the previous config.
+const WIFI_RECONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Body accepted by `POST /api/wifi`.
+#[derive(Deserialize)]
+struct WifiCreds {
+    ssid: String,
+    password: Option<String>,
+    /// Fixed STA IP/gateway to apply instead of DHCP. Omit for DHCP.
+    #[serde(default)]
+    ip: Option<std::net::Ipv4Addr>,
+    #[serde(default)]
+    gateway: Option<std::net::Ipv4Addr>,
+    #[serde(default)]
+    prefix_len: Option<u8>,
+}
+
+impl WifiCreds {
+    /// Build a static IP config from the request body. `None` if none of
+    /// ip/gateway/prefix_len were supplied (DHCP); an error if only some of
+    /// them were, rather than silently falling back to DHCP with part of the
+    /// request ignored.
+    fn sta_ip_settings(&self) -> Result<Option<config::StaIpSettings>> {
+        match (self.ip, self.gateway, self.prefix_len) {
+            (None, None, None) => Ok(None),
+            (Some(ip), Some(gateway), Some(prefix_len)) => {
+                Ok(Some(config::StaIpSettings { ip, gateway, prefix_len }))
+            }
+            _ => anyhow::bail!(
+                "ip, gateway and prefix_len must all be set together, or all omitted for DHCP"
+            ),
+        }
+    }
+}
+
+/// Detached senders for every open `/ws` session, so a reading produced on
+/// the updater thread can be pushed out without going through the request
+/// handler that originally accepted the connection.
+pub type WsSessions = Arc<Mutex<Vec<esp_idf_svc::http::server::ws::EspHttpWsDetachedSender>>>;
+
+/// Create an empty set of WebSocket sessions to hand to `start_http_server`.
+pub fn new_ws_sessions() -> WsSessions {
+    Arc::new(Mutex::new(Vec::new()))
+}
+
+/// Push `data` as a JSON text frame to every open `/ws` session, dropping any
+/// session whose send fails (the client disconnected without a clean close).
+pub fn broadcast_sensor(sessions: &WsSessions, data: &SensorData) {
+    let json = match serde_json::to_string(data) {
+        Ok(json) => json,
+        Err(e) => {
+            log::warn!("Failed to serialize sensor data for broadcast: {e}");
+            return;
+        }
+    };
+
+    let Ok(mut sessions) = sessions.lock() else {
+        log::warn!("WS sessions lock poisoned, dropping broadcast");
+        return;
+    };
+
+    sessions.retain_mut(|sender| {
+        sender
+            .send(FrameType::Text(false), json.as_bytes())
+            .is_ok()
+    });
+}
+
 /// Build a default HTTP server configuration.
 /// Tweak values here if you need different stack size or timeouts.
 pub fn default_config() -> esp_idf_svc::http::server::Configuration {
     esp_idf_svc::http::server::Configuration {
-        // Increase stack size for handlers if you extend them
-        stack_size: 10_240,
+        // Increase stack size: handlers now include the `/ws` upgrade path
+        // and the ESP-IDF HTTP server's async per-session send API.
+        stack_size: 16_384,
         ..Default::default()
     }
 }
 
 /// Create and configure an HTTP server with routes and handlers.
 /// The returned server must be kept alive (held in a variable) for the routes to remain active.
-pub fn start_http_server(sensor: SharedSensor) -> Result<EspHttpServer<'static>> {
+pub fn start_http_server(
+    sensor: SharedSensor,
+    wifi: SharedWifi,
+    ws_sessions: WsSessions,
+    nodes: SharedNodes,
+) -> Result<EspHttpServer<'static>> {
     let config = default_config();
-    start_http_server_with_config(sensor, &config)
+    start_http_server_with_config(sensor, wifi, ws_sessions, nodes, &config)
 }
 
 /// Same as `start_http_server` but allows passing a custom configuration.
 pub fn start_http_server_with_config(
     sensor: SharedSensor,
+    wifi: SharedWifi,
+    ws_sessions: WsSessions,
+    nodes: SharedNodes,
     config: &esp_idf_svc::http::server::Configuration,
 ) -> Result<EspHttpServer<'static>> {
     let mut server = EspHttpServer::new(config)?;
@@ -53,13 +145,174 @@ pub fn start_http_server_with_config(
     })?;
 
     // API endpoint for sensor data
-    server.fn_handler::<anyhow::Error, _>("/api/sensor", Method::Get, move |req| {
-        let data = snapshot(&sensor);
-        let json = serde_json::to_string(&data).unwrap_or_else(|_| "{}".to_string());
+    server.fn_handler::<anyhow::Error, _>("/api/sensor", Method::Get, {
+        let sensor = sensor.clone();
+        move |req| {
+            let data = snapshot(&sensor);
+            let json = serde_json::to_string(&data).unwrap_or_else(|_| "{}".to_string());
+
+            let mut resp = req.into_ok_response()?;
+            // If you want to be explicit about the content type (optional)
+            // let _ = resp.set_content_type("application/json");
+            resp.write(json.as_bytes())?;
+            Ok(())
+        }
+    })?;
+
+    // Provisioning endpoint: accept new STA credentials, persist them and
+    // reconnect the STA interface so the board joins the home network.
+    server.fn_handler::<anyhow::Error, _>("/api/wifi", Method::Post, move |mut req| {
+        let mut body = Vec::new();
+        (&mut req).take(MAX_BODY_LEN + 1).read_to_end(&mut body)?;
+        if body.len() as u64 > MAX_BODY_LEN {
+            anyhow::bail!("request body exceeds {MAX_BODY_LEN} bytes");
+        }
+        let creds: WifiCreds = serde_json::from_slice(&body)?;
+
+        // Keep the AP side / channel / sensor period, only update the STA
+        // credentials. The previous config is kept around so a failed
+        // reconnect can fall back to it rather than leaving the board
+        // stranded off-network.
+        let previous_cfg = config::load();
+        let mut cfg = previous_cfg.clone();
+        cfg.sta_ssid = Some(creds.ssid.clone());
+        cfg.sta_password = creds.password.clone();
+        cfg.sta_ip = creds.sta_ip_settings()?;
+        config::save(&cfg)?;
+
+        // Reconnecting can block for a long time against a bad SSID or an
+        // unreachable AP; do it on a background thread with a bounded
+        // timeout, the same way the SNTP sync below already runs off the
+        // HTTP worker thread, so neither it nor the shared `wifi` lock (used
+        // by every other wifi-touching code path) is tied up indefinitely.
+        let wifi = wifi.clone();
+        let sensor = sensor.clone();
+        thread::spawn(move || {
+            let reconnected = {
+                let Ok(mut wifi) = wifi.lock() else {
+                    log::warn!("wifi lock poisoned, cannot reconnect");
+                    return;
+                };
+                reconnect_sta_with_timeout(
+                    &mut wifi,
+                    &creds.ssid,
+                    creds.password.as_deref(),
+                    cfg.sta_ip.map(Into::into),
+                    WIFI_RECONNECT_TIMEOUT,
+                )
+            };
+
+            if let Err(e) = reconnected {
+                log::warn!(
+                    "Reconnect to \"{}\" failed ({e}), falling back to previous config",
+                    creds.ssid
+                );
+                if let Err(e) = config::save(&previous_cfg) {
+                    log::warn!("Failed to restore previous config: {e}");
+                }
+                let Ok(mut wifi) = wifi.lock() else {
+                    log::warn!("wifi lock poisoned, cannot restore previous config");
+                    return;
+                };
+                let restored = match &previous_cfg.sta_ssid {
+                    Some(prev_ssid) => reconnect_sta_with_timeout(
+                        &mut wifi,
+                        prev_ssid,
+                        previous_cfg.sta_password.as_deref(),
+                        previous_cfg.sta_ip.map(Into::into),
+                        WIFI_RECONNECT_TIMEOUT,
+                    ),
+                    None => drop_sta(&mut wifi),
+                };
+                if let Err(e) = restored {
+                    log::warn!("Failed to restore previous config too: {e}");
+                }
+                return;
+            }
+
+            // Now that STA has (hopefully) an upstream IP, sync wall-clock
+            // time, still off the HTTP worker thread.
+            match crate::sntp::start_sntp(crate::sntp::DEFAULT_NTP_SERVER) {
+                Ok(sntp) => {
+                    crate::sensor::set_time_synced(&sensor, true);
+                    // Keep the handle alive for the rest of the process.
+                    std::mem::forget(sntp);
+                }
+                Err(e) => log::warn!("SNTP sync after provisioning failed: {e}"),
+            }
+        });
+
+        let mut resp = req.into_ok_response()?;
+        resp.write(b"{\"status\":\"reconnecting\"}")?;
+        Ok(())
+    })?;
+
+    // Live sensor stream: register the session's detached sender on connect
+    // so the updater thread can push a frame on every new reading, and prune
+    // it here on close. Eliminates client polling of `/api/sensor`.
+    server.ws_handler("/ws", move |connection| -> Result<(), anyhow::Error> {
+        if connection.is_new() {
+            let sender = connection.create_detached_sender()?;
+            if let Ok(mut sessions) = ws_sessions.lock() {
+                sessions.push(sender);
+            }
+            log::info!("WS client connected");
+        } else if connection.is_closed() {
+            log::info!("WS client disconnected");
+        }
+        Ok(())
+    })?;
+
+    // Stream an uploaded firmware image straight into the next OTA partition.
+    // On success the new partition is set to boot and the device restarts;
+    // on failure the previous partition is left untouched.
+    server.fn_handler::<anyhow::Error, _>("/api/ota", Method::Post, |mut req| {
+        ota::apply_update(&mut req)?;
+
+        let mut resp = req.into_ok_response()?;
+        resp.write(b"{\"status\":\"rebooting\"}")?;
+        drop(resp);
+
+        // Give the response a moment to flush before tearing everything down.
+        thread::spawn(|| {
+            thread::sleep(Duration::from_millis(500));
+            esp_idf_svc::hal::reset::restart();
+        });
+        Ok(())
+    })?;
+
+    // Which partition is currently running, so an operator can confirm an
+    // OTA update actually took effect.
+    server.fn_handler::<anyhow::Error, _>("/api/ota/status", Method::Get, |req| {
+        let label = ota::running_slot_label()?;
+        let json = serde_json::json!({ "running_partition": label }).to_string();
+
+        let mut resp = req.into_ok_response()?;
+        resp.write(json.as_bytes())?;
+        Ok(())
+    })?;
+
+    // Mark the running app invalid, forcing a rollback to the previous OTA
+    // slot. Reboots immediately, so there is no response to write.
+    server.fn_handler::<anyhow::Error, _>("/api/ota/rollback", Method::Post, |_req| {
+        ota::rollback()?;
+        Ok(())
+    })?;
+
+    // ESP-NOW gateway: all known peers with their last reading, keyed by MAC.
+    server.fn_handler::<anyhow::Error, _>("/api/nodes", Method::Get, move |req| {
+        let snapshot: std::collections::BTreeMap<String, SensorData> = {
+            let nodes = nodes
+                .lock()
+                .map_err(|_| anyhow::anyhow!("nodes lock poisoned"))?;
+            nodes
+                .iter()
+                .map(|(mac, data)| (mac_to_hex(mac), data.clone()))
+                .collect()
+        };
+        let json = serde_json::to_string(&snapshot)?;
 
         let mut resp = req.into_ok_response()?;
-        // If you want to be explicit about the content type (optional)
-        // let _ = resp.set_content_type("application/json");
         resp.write(json.as_bytes())?;
         Ok(())
     })?;
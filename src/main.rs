@@ -1,15 +1,31 @@
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
+mod config;
+mod espnow;
 mod http;
+mod mqtt;
+mod ota;
 mod sensor;
+mod sntp;
 mod wifi;
 
-fn wait_forever<T, U, V>(_a: &T, _b: &U, _c: &V) -> ! {
+/// Broker for the optional MQTT telemetry/command integration.
+/// Adjust to point at your own broker; MQTT is skipped if the connection fails.
+const MQTT_BROKER_URL: &str = "mqtt://broker.local:1883";
+const MQTT_TOPIC: &str = "esp32s3/sensor";
+
+fn wait_forever<T, U, V, W, X, Y>(_a: &T, _b: &U, _c: &V, _d: &W, _e: &X, _f: &Y) -> ! {
     loop {
         thread::sleep(Duration::from_secs(60));
     }
 }
 
+/// Fixed AP gateway IP, so the demo is reachable at the same URL every boot
+/// instead of whatever address the default AP netif config happens to pick.
+const AP_GATEWAY: std::net::Ipv4Addr = std::net::Ipv4Addr::new(192, 168, 71, 1);
+const AP_PREFIX_LEN: u8 = 24;
+
 // Main function
 fn main() -> anyhow::Result<()> {
     // It is necessary to call this function once. Otherwise some patches to the runtime
@@ -21,22 +37,119 @@ fn main() -> anyhow::Result<()> {
 
     log::info!("Starting HTTP Server example...");
 
-    // Setup WiFi first
-    let wifi = crate::wifi::setup_default_ap()?;
+    // Load persisted config (Wi-Fi + sensor settings), falling back to
+    // defaults on first boot or if NVS is empty/unreadable.
+    let cfg = config::load();
+
+    // Setup WiFi first, anchored at a fixed AP gateway IP so the demo is
+    // reachable at the same URL every boot. If a home/STA network was
+    // persisted by a previous `/api/wifi` call, rejoin it in AP+STA mode;
+    // otherwise (or if that rejoin fails) fall back to AP-only, which a
+    // phone can then provision via `/api/wifi`.
+    let ap_ip_config = wifi::ApIpConfig {
+        gateway: AP_GATEWAY,
+        prefix_len: AP_PREFIX_LEN,
+    };
+
+    let wifi = match &cfg.sta_ssid {
+        Some(sta_ssid) => wifi::setup_apsta(
+            &cfg.ap_ssid,
+            cfg.ap_password.as_deref(),
+            cfg.channel,
+            ap_ip_config,
+            sta_ssid,
+            cfg.sta_password.as_deref(),
+            cfg.sta_ip.map(Into::into),
+        )
+        .or_else(|e| {
+            log::warn!("AP+STA setup with persisted STA credentials failed ({e}), falling back to AP-only");
+            wifi::setup_ap_with_ip(&cfg.ap_ssid, cfg.ap_password.as_deref(), cfg.channel, ap_ip_config)
+        })?,
+        None => wifi::setup_ap_with_ip(
+            &cfg.ap_ssid,
+            cfg.ap_password.as_deref(),
+            cfg.channel,
+            ap_ip_config,
+        )?,
+    };
+    let wifi: wifi::SharedWifi = Arc::new(Mutex::new(wifi));
     log::info!("WiFi setup complete");
 
     // Create a shared sensor data that will be updated periodically
     let sensor_data = sensor::new_shared();
+    let period = sensor::new_shared_period(cfg.update_period_secs);
+    let ws_sessions = http::new_ws_sessions();
+
+    // Sync wall-clock time before the updater starts taking readings, but
+    // only if the STA side actually has an upstream IP (the board starts in
+    // AP-only mode until provisioned, and SNTP has nothing to sync against).
+    // `get_ip_info()` returns `Ok` with the zero-initialized 0.0.0.0 address
+    // even when STA was never started/connected, so check the address
+    // itself rather than treating `Ok(_)` as "connected" (see `setup_sta`/
+    // `setup_apsta`, which only use this call for post-`wait_netif_up` logging).
+    // The returned handle is kept alive via `wait_forever` below.
+    let has_sta_ip = wifi
+        .lock()
+        .ok()
+        .and_then(|w| w.wifi().sta_netif().get_ip_info().ok())
+        .is_some_and(|ip_info| ip_info.ip != std::net::Ipv4Addr::UNSPECIFIED);
+
+    let sntp_handle = if has_sta_ip {
+        match sntp::start_sntp(sntp::DEFAULT_NTP_SERVER) {
+            Ok(sntp) => {
+                sensor::set_time_synced(&sensor_data, true);
+                Some(sntp)
+            }
+            Err(e) => {
+                log::warn!("SNTP sync failed, timestamps may be inaccurate: {e}");
+                None
+            }
+        }
+    } else {
+        log::info!("No STA uplink yet, skipping SNTP sync until provisioned");
+        None
+    };
 
     // Using std::thread for periodic work to avoid starving FreeRTOS idle task
 
+    // Aggregate readings from battery-powered ESP-NOW sensor nodes. Works
+    // alongside the Wi-Fi AP on the same channel, no association needed.
+    let nodes = espnow::new_shared_nodes();
+    let espnow_handle = espnow::start_espnow(nodes.clone())?;
+
     // Start HTTP server with predefined routes
-    let server = crate::http::start_http_server(sensor_data.clone())?;
+    let server = crate::http::start_http_server(
+        sensor_data.clone(),
+        wifi.clone(),
+        ws_sessions.clone(),
+        nodes,
+    )?;
+
+    // MQTT needs upstream connectivity, so it's started after Wi-Fi. A
+    // connection failure here (e.g. no broker reachable yet) is non-fatal;
+    // the HTTP/WS surface keeps working without it.
+    let mqtt = match mqtt::start_mqtt(MQTT_BROKER_URL, sensor_data.clone(), period.clone()) {
+        Ok(client) => Some(client),
+        Err(e) => {
+            log::warn!("MQTT setup failed, continuing without it: {e}");
+            None
+        }
+    };
 
-    // Start a periodic updater on a separate std thread to avoid starving FreeRTOS idle task
-    let updater = sensor::start_updater(sensor_data.clone(), Duration::from_secs(5));
+    // Start a periodic updater on a separate std thread to avoid starving FreeRTOS idle task.
+    // Each new reading is pushed to every connected `/ws` session and, if
+    // connected, published to the MQTT broker.
+    let updater = sensor::start_updater_with_notify(sensor_data.clone(), period, {
+        let mqtt = mqtt.clone();
+        move |data| {
+            http::broadcast_sensor(&ws_sessions, data);
+            if let Some(mqtt) = &mqtt {
+                mqtt::publish_sensor(mqtt, MQTT_TOPIC, mqtt::QoS::AtMostOnce, data);
+            }
+        }
+    });
 
-    log::info!("HTTP Server started. Access the demo at http://<ESP32-IP>/");
+    log::info!("HTTP Server started. Access the demo at http://{AP_GATEWAY}/");
 
-    wait_forever(&wifi, &server, &updater)
+    wait_forever(&wifi, &server, &updater, &mqtt, &sntp_handle, &espnow_handle)
 }
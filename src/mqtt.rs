@@ -0,0 +1,95 @@
+use anyhow::Result;
+use esp_idf_svc::mqtt::client::{EspMqttClient, EspMqttEvent, EventPayload, MqttClientConfiguration};
+pub use esp_idf_svc::mqtt::client::QoS;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::sensor::{self, SensorData, SharedPeriod, SharedSensor};
+
+/// Topic the device listens on for remote commands: `read` triggers an
+/// immediate reading, `interval:<secs>` changes the updater's cadence.
+pub const CMD_TOPIC: &str = "esp32s3/cmd";
+
+/// Thread-safe handle to the MQTT client, kept alive like the HTTP `server`.
+pub type SharedMqtt = Arc<Mutex<EspMqttClient<'static>>>;
+
+/// Connect to `broker_url`, subscribe to [`CMD_TOPIC`] and return a shared
+/// client handle that `publish_sensor` can push readings through.
+///
+/// The client's event loop (connection status + incoming commands) runs on
+/// its own std thread, mirroring the sensor updater's pattern.
+pub fn start_mqtt(broker_url: &str, sensor: SharedSensor, period: SharedPeriod) -> Result<SharedMqtt> {
+    let mqtt_config = MqttClientConfiguration::default();
+    let (mut client, mut connection) = EspMqttClient::new(broker_url, &mqtt_config)?;
+
+    thread::spawn(move || {
+        while let Ok(event) = connection.next() {
+            handle_event(&event, &sensor, &period);
+        }
+        log::warn!("MQTT event loop ended");
+    });
+
+    client.subscribe(CMD_TOPIC, QoS::AtLeastOnce)?;
+    log::info!("MQTT connected to {broker_url}, subscribed to {CMD_TOPIC}");
+
+    Ok(Arc::new(Mutex::new(client)))
+}
+
+/// Publish a `SensorData` snapshot (same JSON shape as `/api/sensor`) to
+/// `topic`. Intended to be called from the updater's `on_update` callback.
+pub fn publish_sensor(client: &SharedMqtt, topic: &str, qos: QoS, data: &SensorData) {
+    let json = match serde_json::to_string(data) {
+        Ok(json) => json,
+        Err(e) => {
+            log::warn!("Failed to serialize sensor data for MQTT: {e}");
+            return;
+        }
+    };
+
+    let Ok(mut client) = client.lock() else {
+        log::warn!("MQTT client lock poisoned, dropping publish");
+        return;
+    };
+
+    if let Err(e) = client.publish(topic, qos, false, json.as_bytes()) {
+        log::warn!("Failed to publish sensor data over MQTT: {e}");
+    }
+}
+
+fn handle_event(event: &EspMqttEvent, sensor: &SharedSensor, period: &SharedPeriod) {
+    match event.payload() {
+        EventPayload::Connected(_) => log::info!("MQTT connection established"),
+        EventPayload::Disconnected => log::warn!("MQTT disconnected"),
+        EventPayload::Received {
+            topic: Some(topic),
+            data,
+            ..
+        } if topic == CMD_TOPIC => handle_command(data, sensor, period),
+        EventPayload::Error(e) => log::warn!("MQTT error: {e:?}"),
+        _ => {}
+    }
+}
+
+/// Parse and apply a command received on [`CMD_TOPIC`]: `read` forces an
+/// immediate reading, `interval:<secs>` updates the shared update period.
+fn handle_command(payload: &[u8], sensor: &SharedSensor, period: &SharedPeriod) {
+    let Ok(cmd) = std::str::from_utf8(payload) else {
+        log::warn!("Ignoring non-UTF8 MQTT command");
+        return;
+    };
+
+    match cmd.trim() {
+        "read" => {
+            sensor::update_once(sensor);
+            log::info!("MQTT: forced an immediate reading");
+        }
+        other => match other.strip_prefix("interval:").and_then(|s| s.parse::<u64>().ok()) {
+            Some(secs) if secs > 0 => {
+                period.store(secs, Ordering::Relaxed);
+                log::info!("MQTT: update interval set to {secs}s");
+            }
+            _ => log::warn!("Unknown MQTT command: {cmd}"),
+        },
+    }
+}
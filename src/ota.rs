@@ -0,0 +1,45 @@
+use anyhow::Result;
+use esp_idf_svc::ota::EspOta;
+use std::io::Read;
+
+/// Label of the partition the firmware is currently booted from
+/// (e.g. `"ota_0"` / `"ota_1"`), for `GET /api/ota/status`.
+pub fn running_slot_label() -> Result<String> {
+    let ota = EspOta::new()?;
+    let slot = ota.get_running_slot()?;
+    Ok(slot.label.to_string())
+}
+
+/// Stream `body` into the next OTA partition, verify it and set it as the
+/// boot partition. Does not reboot — the caller decides when (typically
+/// after the HTTP response has been flushed to the client).
+pub fn apply_update(body: &mut impl Read) -> Result<()> {
+    let mut ota = EspOta::new()?;
+    let mut update = ota.initiate_update()?;
+
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = body.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        if let Err(e) = update.write(&buf[..n]) {
+            // Abort leaves the previous boot partition untouched.
+            update.abort()?;
+            return Err(e.into());
+        }
+    }
+
+    update.complete()?;
+    log::info!("OTA update written and verified, boot partition updated");
+    Ok(())
+}
+
+/// Mark the currently running app partition invalid, forcing a rollback to
+/// the previous partition. This reboots immediately, so the HTTP response
+/// for `/api/ota/rollback` never gets written. Used to back out of a bad
+/// update without a USB cable.
+pub fn rollback() -> Result<()> {
+    let mut ota = EspOta::new()?;
+    ota.mark_running_slot_invalid_and_reboot()
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -9,6 +10,10 @@ pub struct SensorData {
     pub temperature: f32,
     pub humidity: f32,
     pub timestamp: u64,
+    /// Whether `timestamp` reflects SNTP-synced wall-clock time. False until
+    /// `sntp::start_sntp` completes, since a fresh board with no RTC battery
+    /// starts its clock near the UNIX epoch.
+    pub time_synced: bool,
 }
 
 /// Thread-safe shared handle to the sensor data.
@@ -20,9 +25,20 @@ pub fn new_shared() -> SharedSensor {
         temperature: 25.0,
         humidity: 60.0,
         timestamp: now_secs(),
+        time_synced: false,
     }))
 }
 
+/// Record that the system clock is now SNTP-synced, so subsequent readings
+/// report `time_synced: true`.
+pub fn set_time_synced(shared: &SharedSensor, synced: bool) {
+    if let Ok(mut data) = shared.lock() {
+        data.time_synced = synced;
+    } else {
+        log::warn!("Failed to lock sensor data to record time sync status");
+    }
+}
+
 /// Take a snapshot (clone) of the current sensor data.
 pub fn snapshot(shared: &SharedSensor) -> SensorData {
     shared.lock().map(|d| d.clone()).unwrap_or_else(|e| {
@@ -32,12 +48,21 @@ pub fn snapshot(shared: &SharedSensor) -> SensorData {
             temperature: 25.0,
             humidity: 60.0,
             timestamp: now_secs(),
+            time_synced: false,
         }
     })
 }
 
+/// Shared, mutable update period, so remote operators (e.g. over MQTT) can
+/// change the updater's cadence without restarting the thread.
+pub type SharedPeriod = Arc<AtomicU64>;
+
+/// Create a shared period initialized to `initial_secs`.
+pub fn new_shared_period(initial_secs: u64) -> SharedPeriod {
+    Arc::new(AtomicU64::new(initial_secs.max(1)))
+}
+
 /// Update the shared sensor data once with simulated values.
-#[allow(dead_code)]
 pub fn update_once(shared: &SharedSensor) {
     if let Ok(mut data) = shared.lock() {
         simulate_update(&mut data);
@@ -49,7 +74,24 @@ pub fn update_once(shared: &SharedSensor) {
 /// Start a background thread that periodically updates the sensor data.
 /// Returns the JoinHandle so the caller can keep it if needed.
 /// The thread runs forever; drop the handle to detach.
-pub fn start_updater(shared: SharedSensor, period: Duration) -> thread::JoinHandle<()> {
+pub fn start_updater(shared: SharedSensor, period: SharedPeriod) -> thread::JoinHandle<()> {
+    start_updater_with_notify(shared, period, |_| {})
+}
+
+/// Same as `start_updater`, but invokes `on_update` with each freshly
+/// generated reading. Used to push live data to e.g. WebSocket sessions or
+/// an MQTT broker without the sensor module needing to know about them.
+///
+/// `period` is re-read from the shared atomic before every sleep, so a
+/// remote command (e.g. over MQTT) can change the cadence on the fly.
+pub fn start_updater_with_notify<F>(
+    shared: SharedSensor,
+    period: SharedPeriod,
+    mut on_update: F,
+) -> thread::JoinHandle<()>
+where
+    F: FnMut(&SensorData) + Send + 'static,
+{
     thread::spawn(move || loop {
         // Update values
         {
@@ -61,13 +103,14 @@ pub fn start_updater(shared: SharedSensor, period: Duration) -> thread::JoinHand
                     data.humidity,
                     data.timestamp
                 );
+                on_update(&data);
             } else {
                 log::warn!("Failed to lock sensor data for periodic update");
             }
         }
 
         // Let the scheduler run other tasks (prevents starving IDLE task)
-        thread::sleep(period);
+        thread::sleep(Duration::from_secs(period.load(Ordering::Relaxed)));
     })
 }
 
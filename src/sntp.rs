@@ -0,0 +1,39 @@
+use anyhow::Result;
+use esp_idf_svc::sntp::{EspSntp, SntpConf, SyncStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default NTP server used when no override is supplied.
+pub const DEFAULT_NTP_SERVER: &str = "pool.ntp.org";
+
+/// Give up waiting for a sync after this long, rather than polling forever
+/// against a broker that's unreachable.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Synchronize the system clock against `server`, blocking until the sync
+/// completes or [`SYNC_TIMEOUT`] elapses. Must be called once the STA
+/// interface actually has an upstream IP, and before the sensor updater
+/// starts, so `SensorData.timestamp` carries accurate epoch seconds from the
+/// first reading.
+///
+/// Returns the `EspSntp` handle, which must be kept alive for as long as
+/// periodic re-sync is wanted.
+pub fn start_sntp(server: &str) -> Result<EspSntp<'static>> {
+    let conf = SntpConf {
+        servers: [server],
+        ..Default::default()
+    };
+    let sntp = EspSntp::new(&conf)?;
+
+    log::info!("Waiting for SNTP sync against {server}");
+    let deadline = Instant::now() + SYNC_TIMEOUT;
+    while sntp.get_sync_status() != SyncStatus::Completed {
+        if Instant::now() >= deadline {
+            anyhow::bail!("SNTP sync against {server} timed out after {SYNC_TIMEOUT:?}");
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    log::info!("SNTP sync complete");
+
+    Ok(sntp)
+}
@@ -1,22 +1,57 @@
 use anyhow::Result;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::hal::prelude::Peripherals;
+use esp_idf_svc::ipv4;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
 use esp_idf_svc::wifi::{
-    AccessPointConfiguration, AuthMethod, BlockingWifi, Configuration, EspWifi,
+    AccessPointConfiguration, AuthMethod, BlockingWifi, ClientConfiguration, Configuration,
+    EspWifi,
 };
 use heapless::String as HString;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-/// Wi-Fi helpers for Access Point (AP) setup and logging.
+/// Fixed IPv4 addressing for the AP interface: the gateway/router address
+/// clients see, plus the subnet size. The built-in DHCP server keeps handing
+/// out leases within that subnet, just anchored at a known address instead
+/// of whatever the default happens to be.
+#[derive(Debug, Clone, Copy)]
+pub struct ApIpConfig {
+    pub gateway: Ipv4Addr,
+    /// Subnet prefix length, e.g. 24 for a `/24` (255.255.255.0).
+    pub prefix_len: u8,
+}
+
+/// Fixed IPv4 addressing for the STA interface, bypassing DHCP entirely.
+#[derive(Debug, Clone, Copy)]
+pub struct StaIpConfig {
+    pub ip: Ipv4Addr,
+    pub gateway: Ipv4Addr,
+    /// Subnet prefix length, e.g. 24 for a `/24` (255.255.255.0).
+    pub prefix_len: u8,
+}
+
+/// Wi-Fi helpers for Access Point (AP), Station (STA) and combined AP+STA setup.
 ///
 /// Typical usage:
 /// let _wifi = crate::wifi::setup_default_ap()?;
 /// or
 /// let _wifi = crate::wifi::setup_ap_with("MY_AP", Some("secret123"), 6)?;
+/// or
+/// let _wifi = crate::wifi::setup_sta("HomeRouter", Some("secret123"), None)?;
+/// or
+/// let _wifi = crate::wifi::setup_ap_with_ip("MY_AP", Some("secret123"), 6, ApIpConfig { gateway: "192.168.71.1".parse().unwrap(), prefix_len: 24 })?;
 ///
 /// Note: ensure the ESP logger is initialized in main:
 /// esp_idf_svc::log::EspLogger::initialize_default();
 
+/// Shared handle to a running Wi-Fi driver, used so HTTP handlers can
+/// reconfigure the STA interface (e.g. for credential provisioning) while
+/// the driver is kept alive elsewhere.
+pub type SharedWifi = Arc<Mutex<BlockingWifi<EspWifi<'static>>>>;
+
 /// Setup an Access Point with a default SSID/password/channel.
 /// SSID: "ESP32-S3-DEMO", password: "password123", channel: 1
 pub fn setup_default_ap() -> Result<BlockingWifi<EspWifi<'static>>> {
@@ -70,6 +105,295 @@ pub fn setup_ap_with(
     Ok(wifi)
 }
 
+/// Setup an Access Point with a fixed gateway IP and DHCP pool, instead of
+/// whatever the default happens to be. Lets the demo be reachable at the
+/// same predictable URL every boot.
+pub fn setup_ap_with_ip(
+    ssid: &str,
+    password: Option<&str>,
+    channel: u8,
+    ip_config: ApIpConfig,
+) -> Result<BlockingWifi<EspWifi<'static>>> {
+    let peripherals = Peripherals::take()?;
+    let sys_loop = EspSystemEventLoop::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
+
+    let modem = peripherals.modem;
+
+    let wifi = EspWifi::new(modem, sys_loop.clone(), Some(nvs))?;
+    let mut wifi = BlockingWifi::wrap(wifi, sys_loop)?;
+
+    let cfg = build_ap_config(ssid, password, channel)?;
+    wifi.set_configuration(&cfg)?;
+    apply_ap_ip_config(&mut wifi, &ip_config)?;
+
+    wifi.start()?;
+    log::info!("WiFi started in Access Point mode");
+    unsafe {
+        esp_idf_svc::sys::esp_wifi_set_ps(esp_idf_svc::sys::wifi_ps_type_t_WIFI_PS_NONE);
+        let _ = esp_idf_svc::sys::esp_wifi_set_max_tx_power(84);
+    }
+
+    wifi.wait_netif_up()?;
+    if let Ok(ip_info) = wifi.wifi().ap_netif().get_ip_info() {
+        log::info!(
+            "AP gateway: {}, DHCP pool serving the /{} subnet",
+            ip_info.ip,
+            ip_config.prefix_len
+        );
+    } else {
+        log::warn!("Failed to fetch AP IP info");
+    }
+
+    Ok(wifi)
+}
+
+/// Setup Wi-Fi in Station (STA) mode, joining an existing access point.
+///
+/// Scans for the target network, connects, waits for DHCP to hand out an
+/// address (unless `ip_config` is given, in which case DHCP is skipped and
+/// the fixed address is applied instead) and logs the acquired IP.
+///
+/// - ssid: up to 32 chars
+/// - password: None for an open network, Some for WPA2-Personal (8..=63 chars)
+/// - ip_config: None for DHCP (the default), Some for a fixed IP/gateway
+pub fn setup_sta(
+    ssid: &str,
+    password: Option<&str>,
+    ip_config: Option<StaIpConfig>,
+) -> Result<BlockingWifi<EspWifi<'static>>> {
+    let peripherals = Peripherals::take()?;
+    let sys_loop = EspSystemEventLoop::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
+
+    let modem = peripherals.modem;
+
+    let wifi = EspWifi::new(modem, sys_loop.clone(), Some(nvs))?;
+    let mut wifi = BlockingWifi::wrap(wifi, sys_loop)?;
+
+    let cfg = build_client_config(ssid, password)?;
+    wifi.set_configuration(&cfg)?;
+    if let Some(ip_config) = &ip_config {
+        apply_sta_ip_config(&mut wifi, ip_config)?;
+    }
+
+    wifi.start()?;
+    log::info!("WiFi started in Station mode, scanning for \"{}\"", ssid);
+
+    wifi.connect()?;
+    log::info!("Connected to \"{}\", waiting for an IP", ssid);
+    wifi.wait_netif_up()?;
+
+    if let Ok(ip_info) = wifi.wifi().sta_netif().get_ip_info() {
+        log::info!("STA IP: {}, gateway: {}", ip_info.ip, ip_info.subnet.gateway);
+    } else {
+        log::warn!("Failed to fetch STA IP info");
+    }
+
+    Ok(wifi)
+}
+
+/// Apply a fixed gateway IP to the AP netif's DHCP server before `start()`.
+fn apply_ap_ip_config(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    ip_config: &ApIpConfig,
+) -> Result<()> {
+    let router_cfg = ipv4::RouterConfiguration {
+        subnet: ipv4::Subnet {
+            gateway: ip_config.gateway,
+            mask: ipv4::Mask(ip_config.prefix_len),
+        },
+        dhcp_enabled: true,
+        dns: None,
+        secondary_dns: None,
+    };
+
+    wifi.wifi_mut()
+        .ap_netif_mut()
+        .set_ip_conf(&ipv4::Configuration::Router(router_cfg))?;
+    Ok(())
+}
+
+/// Apply a fixed client IP to the STA netif before `start()`, disabling DHCP.
+fn apply_sta_ip_config(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    ip_config: &StaIpConfig,
+) -> Result<()> {
+    let client_settings = ipv4::ClientSettings {
+        ip: ip_config.ip,
+        subnet: ipv4::Subnet {
+            gateway: ip_config.gateway,
+            mask: ipv4::Mask(ip_config.prefix_len),
+        },
+        dns: None,
+        secondary_dns: None,
+    };
+
+    wifi.wifi_mut().sta_netif_mut().set_ip_conf(&ipv4::Configuration::Client(
+        ipv4::ClientConfiguration::Fixed(client_settings),
+    ))?;
+    Ok(())
+}
+
+/// Setup Wi-Fi in combined AP+STA mode: hosts the local config page on the
+/// AP interface while simultaneously joining an upstream router as a client.
+///
+/// - ap_ssid/ap_password/ap_channel/ap_ip_config: Access Point side, see
+///   [`setup_ap_with_ip`]
+/// - sta_ssid/sta_password/sta_ip_config: upstream network to join, see
+///   [`setup_sta`]
+pub fn setup_apsta(
+    ap_ssid: &str,
+    ap_password: Option<&str>,
+    ap_channel: u8,
+    ap_ip_config: ApIpConfig,
+    sta_ssid: &str,
+    sta_password: Option<&str>,
+    sta_ip_config: Option<StaIpConfig>,
+) -> Result<BlockingWifi<EspWifi<'static>>> {
+    let peripherals = Peripherals::take()?;
+    let sys_loop = EspSystemEventLoop::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
+
+    let modem = peripherals.modem;
+
+    let wifi = EspWifi::new(modem, sys_loop.clone(), Some(nvs))?;
+    let mut wifi = BlockingWifi::wrap(wifi, sys_loop)?;
+
+    let Configuration::AccessPoint(ap_cfg) = build_ap_config(ap_ssid, ap_password, ap_channel)?
+    else {
+        unreachable!("build_ap_config always returns Configuration::AccessPoint")
+    };
+    let Configuration::Client(client_cfg) = build_client_config(sta_ssid, sta_password)? else {
+        unreachable!("build_client_config always returns Configuration::Client")
+    };
+
+    wifi.set_configuration(&Configuration::Mixed(client_cfg, ap_cfg))?;
+    apply_ap_ip_config(&mut wifi, &ap_ip_config)?;
+    if let Some(ip_config) = &sta_ip_config {
+        apply_sta_ip_config(&mut wifi, ip_config)?;
+    }
+
+    wifi.start()?;
+    log::info!("WiFi started in AP+STA mode");
+
+    wifi.connect()?;
+    wifi.wait_netif_up()?;
+
+    if let Ok(ip_info) = wifi.wifi().ap_netif().get_ip_info() {
+        log::info!("AP IP: {}", ip_info.ip);
+    }
+    if let Ok(ip_info) = wifi.wifi().sta_netif().get_ip_info() {
+        log::info!("STA IP: {}", ip_info.ip);
+    } else {
+        log::warn!("STA interface has no IP yet");
+    }
+
+    Ok(wifi)
+}
+
+/// Drop the STA side of a running AP+STA driver, leaving AP-only. Used when
+/// a freshly-provisioned STA config fails and there's no previous STA config
+/// to fall back to.
+pub fn drop_sta(wifi: &mut BlockingWifi<EspWifi<'static>>) -> Result<()> {
+    if let Configuration::Mixed(_, ap_cfg) = wifi.get_configuration()? {
+        wifi.set_configuration(&Configuration::AccessPoint(ap_cfg))?;
+    }
+    Ok(())
+}
+
+/// Build the `Configuration` to apply for a STA (re)connect, preserving the
+/// AP side if one is currently running so provisioning upgrades AP-only into
+/// AP+STA rather than tearing down the config page.
+fn sta_reconnect_config(
+    wifi: &BlockingWifi<EspWifi<'static>>,
+    ssid: &str,
+    password: Option<&str>,
+) -> Result<Configuration> {
+    let new_client_cfg = build_client_config(ssid, password)?;
+
+    Ok(match wifi.get_configuration()? {
+        Configuration::Mixed(_, ap_cfg) | Configuration::AccessPoint(ap_cfg) => {
+            let Configuration::Client(client_cfg) = new_client_cfg else {
+                unreachable!()
+            };
+            Configuration::Mixed(client_cfg, ap_cfg)
+        }
+        _ => new_client_cfg,
+    })
+}
+
+/// Reconfigure the STA side of an already-running Wi-Fi driver with new
+/// credentials and reconnect, bounding how long it waits for the network to
+/// come up instead of blocking forever on `wait_netif_up`. Used by the
+/// `/api/wifi` handler so a bad SSID/password or unreachable AP can't tie up
+/// the caller (and the `wifi` lock) indefinitely.
+///
+/// - ip_config: None for DHCP (the default), Some for a fixed IP/gateway
+pub fn reconnect_sta_with_timeout(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    ssid: &str,
+    password: Option<&str>,
+    ip_config: Option<StaIpConfig>,
+    timeout: Duration,
+) -> Result<()> {
+    let cfg = sta_reconnect_config(wifi, ssid, password)?;
+
+    wifi.set_configuration(&cfg)?;
+    if let Some(ip_config) = &ip_config {
+        apply_sta_ip_config(wifi, ip_config)?;
+    }
+
+    wifi.connect()?;
+
+    let deadline = Instant::now() + timeout;
+    while !wifi.is_up()? {
+        if Instant::now() >= deadline {
+            anyhow::bail!("timed out after {timeout:?} waiting for \"{ssid}\" to come up");
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+
+    if let Ok(ip_info) = wifi.wifi().sta_netif().get_ip_info() {
+        log::info!("Reconnected, new STA IP: {}", ip_info.ip);
+    } else {
+        log::warn!("Reconnected but STA has no IP yet");
+    }
+
+    Ok(())
+}
+
+/// Internal helper to create a Client (STA) configuration with heapless Strings.
+fn build_client_config(ssid: &str, password: Option<&str>) -> Result<Configuration> {
+    let mut ssid_h: HString<32> = HString::new();
+    if ssid.len() > ssid_h.capacity() {
+        anyhow::bail!("SSID too long (max {} chars)", ssid_h.capacity());
+    }
+    ssid_h.push_str(ssid).unwrap();
+
+    let (password_h, auth_method) = match password {
+        Some(pwd) if !pwd.is_empty() => {
+            if pwd.len() < 8 || pwd.len() > 63 {
+                anyhow::bail!("WPA2 password must be 8..=63 characters");
+            }
+            let mut pwd_h: HString<64> = HString::new();
+            if pwd.len() > pwd_h.capacity() {
+                anyhow::bail!("Password too long (max {} chars)", pwd_h.capacity());
+            }
+            pwd_h.push_str(pwd).unwrap();
+            (pwd_h, AuthMethod::WPA2Personal)
+        }
+        _ => (HString::<64>::new(), AuthMethod::None),
+    };
+
+    Ok(Configuration::Client(ClientConfiguration {
+        ssid: ssid_h,
+        auth_method,
+        password: password_h,
+        ..Default::default()
+    }))
+}
+
 /// Internal helper to create AP configuration with heapless Strings.
 fn build_ap_config(ssid: &str, password: Option<&str>, channel: u8) -> Result<Configuration> {
     // SSID (<=32)